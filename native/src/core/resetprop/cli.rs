@@ -0,0 +1,272 @@
+use core::ffi::c_int;
+use std::{
+    fs::{read_to_string, File},
+    io::{self, Write},
+    mem::MaybeUninit,
+};
+
+use base::{debug, libc, LoggedResult, Utf8CStr, Utf8CString};
+
+use crate::resetprop::persist::{delete_prop, get_prop, list_props, set_prop};
+
+const HISTORY_PATH: &str = "/data/adb/resetprop_history";
+const HISTORY_MAX_LINES: usize = 500;
+
+// Minimal linenoise-style line editor: a prompt, an editable buffer, history
+// navigation with the arrow keys, and a Tab-completion callback filtering the
+// known property names by prefix. Raw terminal mode is restored on drop so a
+// panic or early return never leaves the tty in a broken state.
+struct RawTerm {
+    orig: libc::termios,
+}
+
+impl RawTerm {
+    fn enable() -> Option<RawTerm> {
+        unsafe {
+            let mut orig = MaybeUninit::<libc::termios>::uninit();
+            if libc::tcgetattr(libc::STDIN_FILENO, orig.as_mut_ptr()) != 0 {
+                return None;
+            }
+            let orig = orig.assume_init();
+            let mut raw = orig;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return None;
+            }
+            Some(RawTerm { orig })
+        }
+    }
+}
+
+impl Drop for RawTerm {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.orig);
+        }
+    }
+}
+
+fn read_key() -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    loop {
+        match io::Read::read(&mut io::stdin(), &mut buf) {
+            Ok(0) => return Ok(0x04), // EOF -> treat as Ctrl-D
+            Ok(_) => return Ok(buf[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+struct History {
+    lines: Vec<String>,
+}
+
+impl History {
+    fn load() -> History {
+        let lines = read_to_string(HISTORY_PATH)
+            .map(|s| s.lines().map(String::from).collect())
+            .unwrap_or_default();
+        History { lines }
+    }
+
+    fn push(&mut self, line: &str) {
+        if line.is_empty() || self.lines.last().map(String::as_str) == Some(line) {
+            return;
+        }
+        self.lines.push(line.to_string());
+        if self.lines.len() > HISTORY_MAX_LINES {
+            let drop = self.lines.len() - HISTORY_MAX_LINES;
+            self.lines.drain(0..drop);
+        }
+        self.save();
+    }
+
+    // Rewrite the whole (already-capped) history back to disk, so the file
+    // never grows past `HISTORY_MAX_LINES` across sessions the way a blind
+    // append would.
+    fn save(&self) {
+        if let Ok(mut f) = File::create(HISTORY_PATH) {
+            for line in &self.lines {
+                let _ = writeln!(f, "{}", line);
+            }
+        }
+    }
+}
+
+// Complete `prefix` against the current property set, loaded once per
+// completion request so repeated Tabs on a long-lived session stay fresh.
+fn complete(prefix: &str) -> Vec<String> {
+    list_props()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .collect()
+}
+
+// Read one line with basic editing (backspace, history up/down, tab
+// completion). Returns `None` on Ctrl-D with an empty buffer.
+fn read_line(prompt: &str, history: &History) -> io::Result<Option<String>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let _raw = RawTerm::enable();
+    let mut buf = String::new();
+    let mut hist_idx = history.lines.len();
+
+    loop {
+        let redraw = |buf: &str| -> io::Result<()> {
+            print!("\r\x1b[K{}{}", prompt, buf);
+            io::stdout().flush()
+        };
+
+        match read_key()? {
+            b'\r' | b'\n' => {
+                println!();
+                return Ok(Some(buf));
+            }
+            0x04 if buf.is_empty() => {
+                println!();
+                return Ok(None);
+            }
+            0x7f | 0x08 => {
+                buf.pop();
+                redraw(&buf)?;
+            }
+            b'\t' => {
+                let matches = complete(&buf);
+                match matches.as_slice() {
+                    [only] => {
+                        buf = only.clone();
+                        redraw(&buf)?;
+                    }
+                    [] => {}
+                    many => {
+                        println!();
+                        println!("{}", many.join("  "));
+                        redraw(&buf)?;
+                    }
+                }
+            }
+            0x1b => {
+                // Escape sequence: arrow keys are ESC '[' 'A'/'B'.
+                if read_key()? != b'[' {
+                    continue;
+                }
+                match read_key()? {
+                    b'A' => {
+                        // Up
+                        if hist_idx > 0 {
+                            hist_idx -= 1;
+                            buf = history.lines[hist_idx].clone();
+                            redraw(&buf)?;
+                        }
+                    }
+                    b'B' => {
+                        // Down
+                        if hist_idx + 1 < history.lines.len() {
+                            hist_idx += 1;
+                            buf = history.lines[hist_idx].clone();
+                        } else {
+                            hist_idx = history.lines.len();
+                            buf.clear();
+                        }
+                        redraw(&buf)?;
+                    }
+                    _ => {}
+                }
+            }
+            c if c.is_ascii_graphic() || c == b' ' => {
+                buf.push(c as char);
+                redraw(&buf)?;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn run_command(line: &str) -> LoggedResult<()> {
+    let mut parts = line.split_whitespace();
+    match parts.next() {
+        Some("get") => {
+            if let Some(name) = parts.next() {
+                let name = Utf8CString::try_from(name.to_string())?;
+                match get_prop(&name) {
+                    Ok(value) => println!("{}", value),
+                    Err(e) => debug!("resetprop: {} not found ({:?})\n", name, e),
+                }
+            } else {
+                println!("usage: get <name>");
+            }
+        }
+        Some("set") => {
+            let (name, value) = (parts.next(), parts.next());
+            if let (Some(name), Some(value)) = (name, value) {
+                let name = Utf8CString::try_from(name.to_string())?;
+                let value = Utf8CString::try_from(value.to_string())?;
+                set_prop(&name, &value)?;
+            } else {
+                println!("usage: set <name> <value>");
+            }
+        }
+        Some("del") => {
+            if let Some(name) = parts.next() {
+                let name = Utf8CString::try_from(name.to_string())?;
+                delete_prop(&name)?;
+            } else {
+                println!("usage: del <name>");
+            }
+        }
+        Some("list") => {
+            for name in list_props()? {
+                println!("{}", name);
+            }
+        }
+        Some(cmd) => println!("unknown command: {}", cmd),
+        None => {}
+    }
+    Ok(())
+}
+
+// FFI entry point called from resetprop's C++ `main()`: `argc` is the
+// argument count the binary was invoked with (the program name counts as
+// one). Mirrors the legacy getprop/setprop convention of dropping into an
+// interactive shell when called bare from a terminal; anything else (piped
+// stdin, or any argument at all, option or positional) leaves argument parsing to the
+// caller untouched. Returns whether it entered the shell, so the caller
+// knows not to also try to parse `argv` as a one-shot command.
+pub unsafe fn resetprop_interactive_main(argc: c_int) -> bool {
+    if argc > 1 || unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+        return false;
+    }
+    interactive_main();
+    true
+}
+
+// Entered when resetprop is invoked with no arguments at all in a TTY.
+// Loops reading `get`/`set`/`del`/`list` commands against the persistent
+// property store until EOF (Ctrl-D); an empty line just prompts again.
+fn interactive_main() {
+    let mut history = History::load();
+    loop {
+        match read_line("resetprop> ", &history) {
+            Ok(Some(line)) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                history.push(line);
+                if let Err(e) = run_command(line) {
+                    debug!("resetprop: command failed ({:?})\n", e);
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                debug!("resetprop: interactive shell I/O error ({})\n", e);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,9 @@
+pub mod cli;
+pub(crate) mod persist;
+
+// Generated by quick-protobuf-codegen from the persistent_properties.proto
+// schema at build time; included rather than declared as a plain `mod` since
+// the source lives under OUT_DIR, not in this directory.
+pub(crate) mod proto {
+    include!(concat!(env!("OUT_DIR"), "/resetprop_proto/mod.rs"));
+}
@@ -1,6 +1,9 @@
 use core::ffi::c_char;
 use std::{
+    collections::HashSet,
+    fmt,
     fs::{read_to_string, remove_file, rename, File},
+    io,
     io::{BufWriter, Write},
     ops::{Deref, DerefMut},
     os::fd::FromRawFd,
@@ -12,7 +15,7 @@ use quick_protobuf::{BytesReader, MessageRead, MessageWrite, Writer};
 
 use base::{
     cstr, debug, libc::mkstemp, raw_cstr, Directory, LoggedError, LoggedResult, MappedFile,
-    StringExt, Utf8CStr, WalkResult,
+    StringExt, Utf8CStr, Utf8CString, WalkResult,
 };
 
 use crate::ffi::{clone_attr, prop_cb_exec, PropCb};
@@ -32,6 +35,51 @@ macro_rules! PERSIST_PROP {
     };
 }
 
+// Concrete reason a persist-layer operation failed. The FFI wrappers below
+// still collapse to `bool`/`LoggedResult`, but an error that fails an
+// operation is logged via `debug!` (see `log_err`) before it's discarded, so
+// failures stay diagnosable instead of disappearing into
+// `LoggedError::default()`. Errors that are skipped rather than propagated
+// are not logged: an unreadable file during a `/data/property` walk, and a
+// name that `persist_get_prop` does not find in the protobuf store.
+#[derive(Debug)]
+pub(crate) enum PersistPropError {
+    NotFound,
+    Io(io::Error),
+    ProtoDecode(String),
+    ProtoEncode(String),
+}
+
+impl fmt::Display for PersistPropError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistPropError::NotFound => write!(f, "property not found"),
+            PersistPropError::Io(e) => write!(f, "I/O error: {e}"),
+            PersistPropError::ProtoDecode(src) => write!(f, "protobuf decode failed: {src}"),
+            PersistPropError::ProtoEncode(src) => write!(f, "protobuf encode failed: {src}"),
+        }
+    }
+}
+
+impl std::error::Error for PersistPropError {}
+
+impl From<io::Error> for PersistPropError {
+    fn from(e: io::Error) -> Self {
+        PersistPropError::Io(e)
+    }
+}
+
+type PersistResult<T> = Result<T, PersistPropError>;
+
+// Log the concrete `PersistPropError` (with `ctx`) and collapse it down to
+// the opaque `LoggedError` the FFI boundary expects.
+fn log_err<T>(result: PersistResult<T>, ctx: &str) -> LoggedResult<T> {
+    result.map_err(|e| {
+        debug!("resetprop: {} failed: {}\n", ctx, e);
+        LoggedError::default()
+    })
+}
+
 trait PropCbExec {
     fn exec(&mut self, name: &Utf8CStr, value: &Utf8CStr);
 }
@@ -58,7 +106,7 @@ impl DerefMut for PersistentProperties {
 
 trait PropExt {
     fn find_index(&self, name: &Utf8CStr) -> Result<usize, usize>;
-    fn find(&mut self, name: &Utf8CStr) -> LoggedResult<&mut PersistentPropertyRecord>;
+    fn find(&mut self, name: &Utf8CStr) -> PersistResult<&mut PersistentPropertyRecord>;
 }
 
 impl PropExt for PersistentProperties {
@@ -66,11 +114,11 @@ impl PropExt for PersistentProperties {
         self.binary_search_by(|p| p.name.as_deref().cmp(&Some(name.deref())))
     }
 
-    fn find(&mut self, name: &Utf8CStr) -> LoggedResult<&mut PersistentPropertyRecord> {
+    fn find(&mut self, name: &Utf8CStr) -> PersistResult<&mut PersistentPropertyRecord> {
         if let Ok(idx) = self.find_index(name) {
             Ok(&mut self[idx])
         } else {
-            Err(LoggedError::default())
+            Err(PersistPropError::NotFound)
         }
     }
 }
@@ -79,14 +127,14 @@ fn check_proto() -> bool {
     Path::new(PERSIST_PROP!()).exists()
 }
 
-fn file_get_prop(name: &Utf8CStr) -> LoggedResult<String> {
+fn file_get_prop(name: &Utf8CStr) -> PersistResult<String> {
     let path = PathBuf::new().join(PERSIST_PROP_DIR!()).join(name);
     let path = path.as_path();
     debug!("resetprop: read prop from [{}]\n", path.display());
     Ok(read_to_string(path)?)
 }
 
-fn file_set_prop(name: &Utf8CStr, value: Option<&Utf8CStr>) -> LoggedResult<()> {
+fn file_set_prop(name: &Utf8CStr, value: Option<&Utf8CStr>) -> PersistResult<()> {
     let path = PathBuf::new().join(PERSIST_PROP_DIR!()).join(name);
     let path = path.as_path();
     if let Some(value) = value {
@@ -95,7 +143,7 @@ fn file_set_prop(name: &Utf8CStr, value: Option<&Utf8CStr>) -> LoggedResult<()>
             let mut f = unsafe {
                 let fd = mkstemp(tmp.as_mut_ptr() as *mut c_char);
                 if fd < 0 {
-                    return Err(Default::default());
+                    return Err(PersistPropError::Io(io::Error::last_os_error()));
                 }
                 File::from_raw_fd(fd)
             };
@@ -110,30 +158,35 @@ fn file_set_prop(name: &Utf8CStr, value: Option<&Utf8CStr>) -> LoggedResult<()>
     Ok(())
 }
 
-fn proto_read_props() -> LoggedResult<PersistentProperties> {
+fn proto_read_props() -> PersistResult<PersistentProperties> {
     debug!("resetprop: decode with protobuf [{}]", PERSIST_PROP!());
-    let m = MappedFile::open(cstr!(PERSIST_PROP!()))?;
+    let m = MappedFile::open(cstr!(PERSIST_PROP!())).map_err(|e| {
+        PersistPropError::Io(io::Error::new(io::ErrorKind::Other, format!("{e:?}")))
+    })?;
     let m = m.as_ref();
     let mut r = BytesReader::from_bytes(m);
-    let mut props = PersistentProperties::from_reader(&mut r, m)?;
+    let mut props = PersistentProperties::from_reader(&mut r, m)
+        .map_err(|e| PersistPropError::ProtoDecode(format!("{e:?}")))?;
     // Keep the list sorted for binary search
     props.sort_unstable_by(|a, b| a.name.cmp(&b.name));
     Ok(props)
 }
 
-fn proto_write_props(props: &PersistentProperties) -> LoggedResult<()> {
+fn proto_write_props(props: &PersistentProperties) -> PersistResult<()> {
     let mut tmp = String::from(concat!(PERSIST_PROP!(), ".XXXXXX"));
     tmp.nul_terminate();
     {
         let f = unsafe {
             let fd = mkstemp(tmp.as_mut_ptr().cast());
             if fd < 0 {
-                return Err(Default::default());
+                return Err(PersistPropError::Io(io::Error::last_os_error()));
             }
             File::from_raw_fd(fd)
         };
         debug!("resetprop: encode with protobuf [{}]", tmp);
-        props.write_message(&mut Writer::new(BufWriter::new(f)))?;
+        props
+            .write_message(&mut Writer::new(BufWriter::new(f)))
+            .map_err(|e| PersistPropError::ProtoEncode(format!("{e:?}")))?;
     }
     unsafe {
         clone_attr(raw_cstr!(PERSIST_PROP!()), tmp.as_ptr().cast());
@@ -146,7 +199,7 @@ pub unsafe fn persist_get_prop(name: *const c_char, prop_cb: Pin<&mut PropCb>) {
     fn inner(name: *const c_char, mut prop_cb: Pin<&mut PropCb>) -> LoggedResult<()> {
         let name = unsafe { Utf8CStr::from_ptr(name)? };
         if check_proto() {
-            let mut props = proto_read_props()?;
+            let mut props = log_err(proto_read_props(), "get prop")?;
             if let Ok(PersistentPropertyRecord {
                 name: Some(ref mut n),
                 value: Some(ref mut v),
@@ -155,7 +208,7 @@ pub unsafe fn persist_get_prop(name: *const c_char, prop_cb: Pin<&mut PropCb>) {
                 prop_cb.exec(Utf8CStr::from_string(n), Utf8CStr::from_string(v));
             }
         } else {
-            let mut value = file_get_prop(name)?;
+            let mut value = log_err(file_get_prop(name), "get prop")?;
             prop_cb.exec(name, Utf8CStr::from_string(&mut value));
             debug!("resetprop: found prop [{}] = [{}]", name, value);
         }
@@ -164,49 +217,46 @@ pub unsafe fn persist_get_prop(name: *const c_char, prop_cb: Pin<&mut PropCb>) {
     inner(name, prop_cb).ok();
 }
 
+// Enumerate every persistent property, regardless of backend, invoking `f`
+// for each `(name, value)` pair. Shared by `persist_get_props` and the
+// backup snapshot below so both walk the store the same way.
+fn for_each_prop(mut f: impl FnMut(&Utf8CStr, &Utf8CStr)) -> LoggedResult<()> {
+    if check_proto() {
+        let mut props = log_err(proto_read_props(), "list props")?;
+        props.iter_mut().for_each(|p| {
+            if let PersistentPropertyRecord {
+                name: Some(ref mut n),
+                value: Some(ref mut v),
+            } = p
+            {
+                f(Utf8CStr::from_string(n), Utf8CStr::from_string(v));
+            }
+        });
+    } else {
+        let mut dir = Directory::open(cstr!(PERSIST_PROP_DIR!()))?;
+        dir.for_all_file(|file| {
+            if let Ok(name) = Utf8CStr::from_bytes(file.d_name().to_bytes()) {
+                if let Ok(mut value) = file_get_prop(name) {
+                    f(name, Utf8CStr::from_string(&mut value));
+                }
+            }
+            Ok(WalkResult::Continue)
+        })?;
+    }
+    Ok(())
+}
+
 pub unsafe fn persist_get_props(prop_cb: Pin<&mut PropCb>) {
     fn inner(mut prop_cb: Pin<&mut PropCb>) -> LoggedResult<()> {
-        if check_proto() {
-            let mut props = proto_read_props()?;
-            props.iter_mut().for_each(|p| {
-                if let PersistentPropertyRecord {
-                    name: Some(ref mut n),
-                    value: Some(ref mut v),
-                } = p
-                {
-                    prop_cb.exec(Utf8CStr::from_string(n), Utf8CStr::from_string(v));
-                }
-            });
-        } else {
-            let mut dir = Directory::open(cstr!(PERSIST_PROP_DIR!()))?;
-            dir.for_all_file(|f| {
-                if let Ok(name) = Utf8CStr::from_bytes(f.d_name().to_bytes()) {
-                    if let Ok(mut value) = file_get_prop(name) {
-                        prop_cb.exec(name, Utf8CStr::from_string(&mut value));
-                    }
-                }
-                Ok(WalkResult::Continue)
-            })?;
-        }
-        Ok(())
+        for_each_prop(|name, value| prop_cb.exec(name, value))
     }
     inner(prop_cb).ok();
 }
 
 pub unsafe fn persist_delete_prop(name: *const c_char) -> bool {
-    fn inner(name: *const c_char) -> LoggedResult<()> {
-        let name = unsafe { Utf8CStr::from_ptr(name)? };
-        if check_proto() {
-            let mut props = proto_read_props()?;
-            if let Ok(idx) = props.find_index(name) {
-                props.remove(idx);
-                proto_write_props(&props)
-            } else {
-                Err(LoggedError::default())
-            }
-        } else {
-            file_set_prop(name, None)
-        }
+    unsafe fn inner(name: *const c_char) -> LoggedResult<()> {
+        let name = Utf8CStr::from_ptr(name)?;
+        set_props_batch(&[(name, None)])
     }
     inner(name).is_ok()
 }
@@ -214,22 +264,396 @@ pub unsafe fn persist_set_prop(name: *const c_char, value: *const c_char) -> boo
     unsafe fn inner(name: *const c_char, value: *const c_char) -> LoggedResult<()> {
         let name = Utf8CStr::from_ptr(name)?;
         let value = Utf8CStr::from_ptr(value)?;
-        if check_proto() {
-            let mut props = proto_read_props()?;
-            match props.find_index(name) {
-                Ok(idx) => props[idx].value = Some(value.to_string()),
-                Err(idx) => props.insert(
-                    idx,
-                    PersistentPropertyRecord {
-                        name: Some(name.to_string()),
-                        value: Some(value.to_string()),
-                    },
-                ),
+        set_props_batch(&[(name, Some(value))])
+    }
+    inner(name, value).is_ok()
+}
+
+// FFI entry point for batch/transactional property updates: `names[i]` paired
+// with `values[i]`, where a null `values[i]` means delete. Lets callers like
+// module install scripts apply many changes in one `proto_read_props` /
+// `proto_write_props` cycle instead of one per property.
+pub unsafe fn persist_set_props(
+    names: *const *const c_char,
+    values: *const *const c_char,
+    count: usize,
+) -> bool {
+    unsafe fn inner(
+        names: *const *const c_char,
+        values: *const *const c_char,
+        count: usize,
+    ) -> LoggedResult<()> {
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let name = Utf8CStr::from_ptr(*names.add(i))?;
+            let value_ptr = *values.add(i);
+            let value = if value_ptr.is_null() {
+                None
+            } else {
+                Some(Utf8CStr::from_ptr(value_ptr)?)
+            };
+            entries.push((name, value));
+        }
+        set_props_batch(&entries)
+    }
+    inner(names, values, count).is_ok()
+}
+
+// Safe, pointer-free helpers shared by the FFI wrappers above and the
+// interactive shell in `cli`.
+pub(crate) fn get_prop(name: &Utf8CStr) -> LoggedResult<String> {
+    if check_proto() {
+        let mut props = log_err(proto_read_props(), "get prop")?;
+        match log_err(props.find(name), "get prop")? {
+            PersistentPropertyRecord { value: Some(v), .. } => Ok(v.clone()),
+            _ => log_err(Err(PersistPropError::NotFound), "get prop"),
+        }
+    } else {
+        log_err(file_get_prop(name), "get prop")
+    }
+}
+
+pub(crate) fn list_props() -> LoggedResult<Vec<String>> {
+    if check_proto() {
+        let props = log_err(proto_read_props(), "list props")?;
+        Ok(props.iter().filter_map(|p| p.name.clone()).collect())
+    } else {
+        let mut names = Vec::new();
+        let mut dir = Directory::open(cstr!(PERSIST_PROP_DIR!()))?;
+        dir.for_all_file(|f| {
+            if let Ok(name) = Utf8CStr::from_bytes(f.d_name().to_bytes()) {
+                names.push(name.to_string());
             }
-            proto_write_props(&props)
+            Ok(WalkResult::Continue)
+        })?;
+        names.sort_unstable();
+        Ok(names)
+    }
+}
+
+pub(crate) fn set_prop(name: &Utf8CStr, value: &Utf8CStr) -> LoggedResult<()> {
+    set_props_batch(&[(name, Some(value))])
+}
+
+pub(crate) fn delete_prop(name: &Utf8CStr) -> LoggedResult<()> {
+    set_props_batch(&[(name, None)])
+}
+
+// Apply every insert/update/delete to `props` in place, keeping it sorted by
+// name for `find_index`'s binary search. Returns whether anything actually
+// changed, so the caller can skip persisting a no-op batch. A delete for a
+// name that isn't present fails the whole batch with `NotFound`, matching
+// the old single-prop `persist_delete_prop` semantics.
+fn apply_batch(
+    props: &mut PersistentProperties,
+    entries: &[(&Utf8CStr, Option<&Utf8CStr>)],
+) -> PersistResult<bool> {
+    let mut changed = false;
+    for (name, value) in entries {
+        match value {
+            Some(value) => match props.find_index(name) {
+                Ok(idx) => {
+                    let value = value.to_string();
+                    if props[idx].value.as_ref() != Some(&value) {
+                        props[idx].value = Some(value);
+                        changed = true;
+                    }
+                }
+                Err(idx) => {
+                    props.insert(
+                        idx,
+                        PersistentPropertyRecord {
+                            name: Some(name.to_string()),
+                            value: Some(value.to_string()),
+                        },
+                    );
+                    changed = true;
+                }
+            },
+            None => match props.find_index(name) {
+                Ok(idx) => {
+                    props.remove(idx);
+                    changed = true;
+                }
+                Err(_) => return Err(PersistPropError::NotFound),
+            },
+        }
+    }
+    Ok(changed)
+}
+
+// Apply every insert/update/delete against the in-memory property list and
+// persist the result with a single `proto_write_props` (or a `file_set_prop`
+// per entry on the legacy file-backed store), instead of doing a full
+// read-modify-write cycle per entry. On the protobuf store, if nothing in the
+// batch actually changed anything, the store is left untouched instead of
+// being rewritten for no reason; the file-backed store writes every entry.
+pub(crate) fn set_props_batch(entries: &[(&Utf8CStr, Option<&Utf8CStr>)]) -> LoggedResult<()> {
+    if check_proto() {
+        let mut props = log_err(proto_read_props(), "batch set props")?;
+        if log_err(apply_batch(&mut props, entries), "batch set props")? {
+            log_err(proto_write_props(&props), "batch set props")
         } else {
-            file_set_prop(name, Some(value))
+            Ok(())
+        }
+    } else {
+        for (name, value) in entries {
+            log_err(file_set_prop(name, *value), "batch set props")?;
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cstr(s: &str) -> Utf8CString {
+        Utf8CString::try_from(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn apply_batch_insert_keeps_sorted_order() {
+        let mut props = PersistentProperties::default();
+        let b = cstr("b.prop");
+        let a = cstr("a.prop");
+        let v = cstr("1");
+        let changed = apply_batch(&mut props, &[(&b, Some(&v)), (&a, Some(&v))]).unwrap();
+        assert!(changed);
+        let names: Vec<_> = props.iter().map(|p| p.name.clone().unwrap()).collect();
+        assert_eq!(names, vec!["a.prop", "b.prop"]);
+    }
+
+    #[test]
+    fn apply_batch_update_existing() {
+        let mut props = PersistentProperties::default();
+        let name = cstr("a.prop");
+        let v1 = cstr("1");
+        let v2 = cstr("2");
+        apply_batch(&mut props, &[(&name, Some(&v1))]).unwrap();
+        apply_batch(&mut props, &[(&name, Some(&v2))]).unwrap();
+        assert_eq!(props.len(), 1);
+        assert_eq!(props[0].value.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn apply_batch_delete_existing() {
+        let mut props = PersistentProperties::default();
+        let name = cstr("a.prop");
+        let v = cstr("1");
+        apply_batch(&mut props, &[(&name, Some(&v))]).unwrap();
+        let changed = apply_batch(&mut props, &[(&name, None)]).unwrap();
+        assert!(changed);
+        assert!(props.is_empty());
+    }
+
+    #[test]
+    fn apply_batch_delete_missing_is_not_found() {
+        let mut props = PersistentProperties::default();
+        let name = cstr("missing.prop");
+        let err = apply_batch(&mut props, &[(&name, None)]).unwrap_err();
+        assert!(matches!(err, PersistPropError::NotFound));
+    }
+}
+
+// Portable backup/restore of the whole persistent property store, independent
+// of whether the device backs it with the protobuf or the legacy per-file
+// representation. The snapshot is one `name=value` line per record, with `\`
+// and newlines in the value escaped so the file stays strictly line-oriented.
+fn escape_snapshot_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape_snapshot_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod snapshot_escape_tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for value in [
+            "",
+            "plain",
+            "a\\b",
+            "line1\nline2",
+            "cr\rlf\r\n",
+            "\\\n\r\\",
+        ] {
+            let escaped = escape_snapshot_value(value);
+            assert!(!escaped.contains('\n') && !escaped.contains('\r'));
+            assert_eq!(unescape_snapshot_value(&escaped), value);
+        }
+    }
+}
+
+pub fn persist_backup_props(path: &Utf8CStr) -> LoggedResult<()> {
+    let mut snapshot = String::new();
+    for_each_prop(|name, value| {
+        snapshot.push_str(name);
+        snapshot.push('=');
+        snapshot.push_str(&escape_snapshot_value(value));
+        snapshot.push('\n');
+    })?;
+    // Same mkstemp+rename idiom as `file_set_prop`/`proto_write_props`, so a
+    // crash mid-write can never leave a torn snapshot at `path`.
+    let mut tmp = format!("{}.XXXXXX", path.deref());
+    tmp.nul_terminate();
+    {
+        let mut f = unsafe {
+            let fd = mkstemp(tmp.as_mut_ptr().cast());
+            if fd < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            File::from_raw_fd(fd)
+        };
+        f.write_all(snapshot.as_bytes())?;
+    }
+    rename(&tmp, Path::new(path.deref()))?;
+    Ok(())
+}
+
+pub fn persist_restore_props(path: &Utf8CStr) -> LoggedResult<()> {
+    let snapshot = read_to_string(Path::new(path.deref()))?;
+    let mut owned = Vec::new();
+    let mut snapshot_names = HashSet::new();
+    for line in snapshot.lines() {
+        if let Some((name, value)) = line.split_once('=') {
+            snapshot_names.insert(name.to_string());
+            let name = Utf8CString::try_from(name.to_string())?;
+            let value = Utf8CString::try_from(unescape_snapshot_value(value))?;
+            owned.push((name, Some(value)));
+        }
+    }
+    // Anything live on-device but absent from the snapshot must be deleted
+    // too, so restore reconstructs the backed-up state exactly instead of
+    // merging into whatever happens to be set now.
+    for name in list_props()? {
+        if !snapshot_names.contains(&name) {
+            owned.push((Utf8CString::try_from(name)?, None));
+        }
+    }
+    let entries: Vec<(&Utf8CStr, Option<&Utf8CStr>)> = owned
+        .iter()
+        .map(|(name, value)| (name.deref(), value.as_deref()))
+        .collect();
+    // On the protobuf store, one transactional write restores the whole
+    // snapshot atomically; the file-backed store applies the entries one
+    // file at a time, so a failure part-way leaves the earlier ones applied.
+    set_props_batch(&entries)
+}
+
+// Convert between the legacy file-backed store and the protobuf store.
+// `check_proto` only ever reports which backend is currently live; this is
+// the only place that actually switches a device between the two. Each
+// direction writes the new representation in full before removing the old
+// one, so a crash mid-migration never leaves a half-migrated store.
+pub fn persist_migrate(to_proto: bool) -> LoggedResult<()> {
+    if to_proto {
+        migrate_file_to_proto()
+    } else {
+        migrate_proto_to_file()
+    }
+}
+
+// Build a `PersistentProperties` list from `(name, value)` pairs, sorted by
+// name so `find_index`'s binary search invariant holds from the moment the
+// protobuf store is first written during migration.
+fn collect_sorted_props(
+    entries: impl IntoIterator<Item = (String, String)>,
+) -> PersistentProperties {
+    let mut props = PersistentProperties::default();
+    for (name, value) in entries {
+        props.push(PersistentPropertyRecord {
+            name: Some(name),
+            value: Some(value),
+        });
+    }
+    props.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    props
+}
+
+fn migrate_file_to_proto() -> LoggedResult<()> {
+    let mut found = Vec::new();
+    let mut dir = Directory::open(cstr!(PERSIST_PROP_DIR!()))?;
+    dir.for_all_file(|file| {
+        if let Ok(name) = Utf8CStr::from_bytes(file.d_name().to_bytes()) {
+            if let Ok(value) = file_get_prop(name) {
+                found.push((name.to_string(), value));
+            }
+        }
+        Ok(WalkResult::Continue)
+    })?;
+    let props = collect_sorted_props(found);
+    log_err(proto_write_props(&props), "migrate to proto")?;
+    unsafe {
+        clone_attr(raw_cstr!(PERSIST_PROP_DIR!()), raw_cstr!(PERSIST_PROP!()));
+    }
+    // The protobuf store is fully written and attributed; only now is it
+    // safe to drop the per-file records it replaces.
+    for p in props.iter() {
+        if let Some(name) = p.name.as_deref() {
+            if let Ok(name) = Utf8CString::try_from(name.to_string()) {
+                let _ = log_err(
+                    file_set_prop(&name, None),
+                    "migrate to proto: remove old file",
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn migrate_proto_to_file() -> LoggedResult<()> {
+    let props = log_err(proto_read_props(), "migrate to file")?;
+    for p in props.iter() {
+        if let (Some(name), Some(value)) = (p.name.as_deref(), p.value.as_deref()) {
+            let name = Utf8CString::try_from(name.to_string())?;
+            let value = Utf8CString::try_from(value.to_string())?;
+            log_err(file_set_prop(&name, Some(&value)), "migrate to file")?;
+            let full_path =
+                Utf8CString::try_from(format!(concat!(PERSIST_PROP_DIR!(), "/{}"), name.deref()))?;
+            unsafe {
+                clone_attr(raw_cstr!(PERSIST_PROP_DIR!()), full_path.as_ptr());
+            }
+        }
+    }
+    // Every record now has its own attributed file; only now is it safe to
+    // remove the protobuf store it replaces.
+    remove_file(PERSIST_PROP!())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod migrate_tests {
+    use super::*;
+
+    #[test]
+    fn collect_sorted_props_sorts_by_name() {
+        let props = collect_sorted_props([
+            ("b.prop".to_string(), "2".to_string()),
+            ("a.prop".to_string(), "1".to_string()),
+        ]);
+        let names: Vec<_> = props.iter().map(|p| p.name.clone().unwrap()).collect();
+        assert_eq!(names, vec!["a.prop", "b.prop"]);
     }
-    inner(name, value).is_ok()
 }